@@ -0,0 +1,347 @@
+//! [OpenTelemetry] extensions for the [awc] client, completing the
+//! propagation loop that [`crate::RequestTracing`] handles on the server
+//! side.
+//!
+//! [OpenTelemetry]: https://opentelemetry.io
+//! [awc]: https://docs.rs/awc
+use actix_web::http::{header, HeaderMap, HeaderName, HeaderValue, Method};
+use awc::http::Uri;
+use awc::{error::SendRequestError, Client, ClientRequest, ClientResponse};
+use futures::{future::FutureExt, Future};
+use opentelemetry::{
+    global,
+    propagation::Injector,
+    trace::{FutureExt as OtelFutureExt, SpanKind, StatusCode, TraceContextExt, Tracer},
+    Context, Key,
+};
+use opentelemetry_semantic_conventions::trace::{
+    HTTP_METHOD, HTTP_STATUS_CODE, HTTP_URL, NET_PEER_NAME, NET_PEER_PORT,
+};
+use rand::Rng;
+use std::pin::Pin;
+use std::time::Duration;
+
+/// The `http.resend_count` attribute set on retry attempt spans, see
+/// [`InstrumentedClientRequest::send_with_retry`].
+const HTTP_RESEND_COUNT: &str = "http.resend_count";
+
+/// Methods that are safe to resend without risking duplicated side effects.
+fn is_idempotent(method: &Method) -> bool {
+    matches!(
+        *method,
+        Method::GET | Method::HEAD | Method::PUT | Method::DELETE
+    )
+}
+
+/// Whether a completed attempt should be retried: connection errors on
+/// idempotent methods, or an explicit 429/503 response (regardless of
+/// method, since these signal the server wants the caller to back off).
+fn is_retryable(method: &Method, result: &Result<ClientResponse, SendRequestError>) -> bool {
+    match result {
+        Ok(res) => matches!(res.status().as_u16(), 429 | 503),
+        Err(_) => is_idempotent(method),
+    }
+}
+
+/// The wait duration a server asked for via a `Retry-After` header given in
+/// whole seconds, if any; an HTTP-date value is not parsed and yields `None`.
+fn retry_after(result: &Result<ClientResponse, SendRequestError>) -> Option<Duration> {
+    let res = result.as_ref().ok()?;
+    let value = res.headers().get(header::RETRY_AFTER)?.to_str().ok()?;
+    value.parse::<u64>().ok().map(Duration::from_secs)
+}
+
+/// Decorrelated-jitter backoff: `sleep = min(max_delay, random(base_delay, prev_sleep * 3))`.
+fn next_backoff(prev_sleep: Duration, base_delay: Duration, max_delay: Duration) -> Duration {
+    let upper = (prev_sleep.as_secs_f64() * 3.0).max(base_delay.as_secs_f64());
+    let jittered = rand::thread_rng().gen_range(base_delay.as_secs_f64()..=upper);
+    Duration::from_secs_f64(jittered).min(max_delay)
+}
+
+/// Rebuild a request from its original method, URI and headers, since
+/// `awc::ClientRequest` is consumed by `send` and can't be cloned for reuse
+/// across retry attempts.
+fn rebuild_request(client: &Client, method: &Method, uri: &Uri, headers: &HeaderMap) -> ClientRequest {
+    let mut request = client.request(method.clone(), uri.clone());
+    for (name, value) in headers.iter() {
+        request = request.header(name.clone(), value.clone());
+    }
+    request
+}
+
+/// A retry policy for [`InstrumentedClientRequest::send_with_retry`], using
+/// decorrelated-jitter backoff between attempts.
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    max_retries: u32,
+    base_delay: Duration,
+    max_delay: Duration,
+}
+
+impl RetryPolicy {
+    /// Retry up to `max_retries` times, waiting between `base_delay` and
+    /// `max_delay` (growing roughly geometrically, with jitter) between
+    /// attempts. A server's `Retry-After` delay replaces that wait as-is and
+    /// is not bounded by `base_delay` or `max_delay`.
+    pub fn new(max_retries: u32, base_delay: Duration, max_delay: Duration) -> Self {
+        RetryPolicy {
+            max_retries,
+            base_delay,
+            max_delay,
+        }
+    }
+}
+
+impl Default for RetryPolicy {
+    /// 3 retries, starting at 50ms and capped at 5s.
+    fn default() -> Self {
+        RetryPolicy::new(3, Duration::from_millis(50), Duration::from_secs(5))
+    }
+}
+
+/// OpenTelemetry extensions for [`awc::Client`].
+pub trait ClientExt {
+    /// Trace an outgoing awc request, injecting the current span's context
+    /// as W3C trace-context headers and recording the response status.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use awc::{Client, error::SendRequestError};
+    /// use actix_web_opentelemetry::ClientExt;
+    ///
+    /// async fn execute_request(client: &Client) -> Result<(), SendRequestError> {
+    ///     let res = client
+    ///         .get("http://localhost:8080")
+    ///         // Add `trace_request` before `send` to any awc request to add instrumentation
+    ///         .trace_request()
+    ///         .send()
+    ///         .await?;
+    ///
+    ///     println!("Response: {:?}", res);
+    ///     Ok(())
+    /// }
+    /// ```
+    fn trace_request(self) -> InstrumentedClientRequest;
+}
+
+impl ClientExt for ClientRequest {
+    fn trace_request(self) -> InstrumentedClientRequest {
+        InstrumentedClientRequest { request: self }
+    }
+}
+
+/// A wrapper around [`awc::ClientRequest`] that traces the request/response
+/// cycle in an OpenTelemetry [`SpanKind::Client`] span, injecting the
+/// current trace-context onto the outgoing request before sending it.
+#[derive(Debug)]
+pub struct InstrumentedClientRequest {
+    request: ClientRequest,
+}
+
+type ClientResponseFuture =
+    Pin<Box<dyn Future<Output = Result<ClientResponse, SendRequestError>>>>;
+
+impl InstrumentedClientRequest {
+    /// Send the traced client request.
+    pub fn send(self) -> ClientResponseFuture {
+        self.send_attempt(0)
+    }
+
+    /// Send the traced client request, retrying according to `policy` on
+    /// connection errors for idempotent methods (GET/HEAD/PUT/DELETE) and on
+    /// 429/503 responses for any method, waiting between attempts using
+    /// decorrelated-jitter backoff (or the server's `Retry-After` header,
+    /// when it gives a delay in seconds; an HTTP-date value is ignored).
+    ///
+    /// Since `awc::ClientRequest` can't be resent as-is once consumed, each
+    /// attempt is reissued from `client` using the original method, URI and
+    /// headers. Every attempt is recorded as its own `SpanKind::Client` span
+    /// carrying an `http.resend_count` attribute, nested under one parent
+    /// span for the whole operation whose status reflects the final outcome.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use awc::{Client, error::SendRequestError};
+    /// use actix_web_opentelemetry::{ClientExt, RetryPolicy};
+    ///
+    /// async fn execute_request(client: &Client) -> Result<(), SendRequestError> {
+    ///     let res = client
+    ///         .get("http://localhost:8080")
+    ///         .trace_request()
+    ///         .send_with_retry(client, RetryPolicy::default())
+    ///         .await?;
+    ///
+    ///     println!("Response: {:?}", res);
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn send_with_retry(self, client: &Client, policy: RetryPolicy) -> ClientResponseFuture {
+        let method = self.request.get_method().clone();
+        let uri = self.request.get_uri().clone();
+        let headers = self.request.headers().clone();
+        let client = client.clone();
+
+        let tracer = global::tracer("actix-client");
+        let mut builder = tracer.span_builder(format!("{} (with retry)", method));
+        builder.span_kind = Some(SpanKind::Client);
+        let parent_span = tracer.build(builder);
+        let parent_cx = Context::current_with_span(parent_span);
+        let loop_cx = parent_cx.clone();
+
+        Box::pin(
+            async move {
+                let mut sleep = policy.base_delay;
+                let mut attempt = 0;
+                loop {
+                    let req = rebuild_request(&client, &method, &uri, &headers);
+                    let res = InstrumentedClientRequest { request: req }
+                        .send_attempt(attempt)
+                        .await;
+
+                    let done = attempt >= policy.max_retries || !is_retryable(&method, &res);
+                    if done {
+                        let span = loop_cx.span();
+                        match &res {
+                            Ok(response) if response.status().is_server_error() => {
+                                span.set_status(StatusCode::Error, String::new());
+                            }
+                            Err(err) => {
+                                span.set_status(StatusCode::Error, err.to_string());
+                            }
+                            Ok(_) => {}
+                        }
+                        span.end();
+                        return res;
+                    }
+
+                    let wait = retry_after(&res).unwrap_or_else(|| {
+                        let wait = next_backoff(sleep, policy.base_delay, policy.max_delay);
+                        sleep = wait;
+                        wait
+                    });
+                    actix_web::rt::time::delay_for(wait).await;
+                    attempt += 1;
+                }
+            }
+            .with_context(parent_cx),
+        )
+    }
+
+    fn send_attempt(mut self, resend_count: u32) -> ClientResponseFuture {
+        let method = self.request.get_method().clone();
+        let uri = self.request.get_uri().clone();
+
+        let tracer = global::tracer("actix-client");
+        let mut builder = tracer.span_builder(method.to_string());
+        builder.span_kind = Some(SpanKind::Client);
+
+        let mut attributes = vec![
+            HTTP_METHOD.string(method.as_str().to_string()),
+            HTTP_URL.string(uri.to_string()),
+            Key::new(HTTP_RESEND_COUNT).i64(resend_count as i64),
+        ];
+        if let Some(host) = uri.host() {
+            attributes.push(NET_PEER_NAME.string(host.to_string()));
+        }
+        if let Some(port) = uri.port_u16() {
+            attributes.push(NET_PEER_PORT.i64(port as i64));
+        }
+        builder.attributes = Some(attributes);
+        let span = tracer.build(builder);
+        let cx = Context::current_with_span(span);
+
+        global::get_text_map_propagator(|propagator| {
+            propagator.inject_context(
+                &cx,
+                &mut RequestHeaderInjector::new(self.request.headers_mut()),
+            )
+        });
+
+        let request_fut = self.request.send();
+        Box::pin(
+            request_fut
+                .with_context(cx.clone())
+                .map(move |res| match &res {
+                    Ok(response) => {
+                        let span = cx.span();
+                        span.set_attribute(HTTP_STATUS_CODE.i64(response.status().as_u16() as i64));
+                        if response.status().is_server_error() {
+                            span.set_status(StatusCode::Error, String::new());
+                        }
+                        span.end();
+                        res
+                    }
+                    Err(err) => {
+                        let span = cx.span();
+                        span.set_status(StatusCode::Error, err.to_string());
+                        span.end();
+                        res
+                    }
+                }),
+        )
+    }
+}
+
+struct RequestHeaderInjector<'a> {
+    headers: &'a mut actix_web::http::HeaderMap,
+}
+
+impl<'a> RequestHeaderInjector<'a> {
+    fn new(headers: &'a mut actix_web::http::HeaderMap) -> Self {
+        RequestHeaderInjector { headers }
+    }
+}
+
+impl<'a> Injector for RequestHeaderInjector<'a> {
+    fn set(&mut self, key: &str, value: String) {
+        if let Ok(name) = HeaderName::from_bytes(key.as_bytes()) {
+            if let Ok(value) = HeaderValue::from_str(&value) {
+                self.headers.insert(name, value);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn idempotent_methods_are_safe_to_resend() {
+        assert!(is_idempotent(&Method::GET));
+        assert!(is_idempotent(&Method::HEAD));
+        assert!(is_idempotent(&Method::PUT));
+        assert!(is_idempotent(&Method::DELETE));
+        assert!(!is_idempotent(&Method::POST));
+        assert!(!is_idempotent(&Method::PATCH));
+    }
+
+    #[test]
+    fn connection_errors_retry_only_idempotent_methods() {
+        let err = || Err(SendRequestError::Timeout);
+        assert!(is_retryable(&Method::GET, &err()));
+        assert!(!is_retryable(&Method::POST, &err()));
+    }
+
+    #[test]
+    fn next_backoff_stays_within_base_and_max_delay() {
+        let base = Duration::from_millis(50);
+        let max = Duration::from_secs(5);
+        let mut sleep = base;
+        for _ in 0..20 {
+            sleep = next_backoff(sleep, base, max);
+            assert!(sleep >= base);
+            assert!(sleep <= max);
+        }
+    }
+
+    #[test]
+    fn next_backoff_respects_max_delay_cap() {
+        let base = Duration::from_millis(50);
+        let max = Duration::from_millis(100);
+        let sleep = next_backoff(max * 10, base, max);
+        assert!(sleep <= max);
+    }
+}
@@ -8,9 +8,12 @@
 //!
 //! * Client requests can be traced by using the [`ClientExt::trace_request`] method.
 //!
-//! The `metrics` feature allows you to expose request metrics to [Prometheus].
+//! The `metrics` feature allows you to expose request metrics to [Prometheus],
+//! or push them to an OpenTelemetry Collector over OTLP.
 //!
 //! * Metrics can be tracked using the [`RequestMetrics`] middleware.
+//! * Use [`RequestMetrics::builder`] and [`RequestMetricsBuilder::with_otlp`]
+//!   to push metrics over OTLP instead of serving a Prometheus scrape route.
 //!
 //! [OpenTelemetry]: https://opentelemetry.io
 //! [Actix Web]: https://actix.rs
@@ -134,9 +137,11 @@ pub(crate) mod util;
 
 #[cfg(feature = "awc")]
 #[cfg_attr(docsrs, doc(cfg(feature = "awc")))]
-pub use client::{ClientExt, InstrumentedClientRequest};
+pub use client::{ClientExt, InstrumentedClientRequest, RetryPolicy};
 
 #[cfg(feature = "metrics")]
 #[cfg_attr(docsrs, doc(cfg(feature = "metrics")))]
-pub use middleware::metrics::{RequestMetrics, RequestMetricsMiddleware};
+pub use middleware::metrics::{
+    OtlpProtocol, RequestMetrics, RequestMetricsBuilder, RequestMetricsMiddleware,
+};
 pub use {middleware::route_formatter::RouteFormatter, middleware::trace::RequestTracing};
@@ -0,0 +1,394 @@
+use super::route_formatter::RouteFormatter;
+use actix_web::{
+    dev::{Service, ServiceRequest, ServiceResponse, Transform},
+    Error, HttpResponse,
+};
+use futures::{
+    future::{ok, Ready},
+    Future,
+};
+use opentelemetry::global;
+use opentelemetry::metrics::{BoundUpDownCounter, Counter, Meter, MeterProvider, ValueRecorder};
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_prometheus::PrometheusExporter;
+use opentelemetry_semantic_conventions::trace::{HTTP_METHOD, HTTP_ROUTE, HTTP_STATUS_CODE};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+const HTTP_SERVER_DURATION: &str = "http.server.duration";
+const HTTP_SERVER_ACTIVE_REQUESTS: &str = "http.server.active_requests";
+const HTTP_SERVER_REQUEST_COUNT: &str = "http.server.request_count";
+
+/// The wire protocol used to push metrics to an OpenTelemetry Collector.
+///
+/// Used by [`RequestMetricsBuilder::with_otlp`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OtlpProtocol {
+    /// Push metrics over gRPC.
+    Grpc,
+    /// Push metrics over HTTP using protobuf-encoded bodies.
+    ///
+    /// Note: `opentelemetry-otlp`'s metrics pipeline only supports a tonic
+    /// (gRPC) exporter today, so this currently falls back to the same
+    /// transport as [`OtlpProtocol::Grpc`]; the variant is kept so callers
+    /// can switch over once HTTP export lands upstream.
+    HttpProtobuf,
+}
+
+/// A predicate used to decide which requests serve the Prometheus scrape
+/// response, used by [`RequestMetrics::new`].
+///
+/// `RequestMetrics` is cloned into the `Send` factory closure passed to
+/// `HttpServer::new`, so this (and [`RequestMetrics::route_formatter`])
+/// needs to be `Send + Sync`, unlike the `Rc`-based equivalents on
+/// [`super::trace::RequestTracing`], which is constructed fresh inside that
+/// closure instead of captured by it.
+type MetricsRouteFilter = Arc<dyn Fn(&ServiceRequest) -> bool + Send + Sync>;
+
+/// The instruments shared by every clone of a [`RequestMetrics`] middleware.
+type RequestInstruments = (
+    ValueRecorder<f64>,
+    Counter<u64>,
+    BoundUpDownCounter<'static, i64>,
+);
+
+/// Request metrics tracking.
+///
+/// # Examples
+///
+/// ```no_run
+/// use actix_web::{dev, http, web, App, HttpRequest, HttpServer};
+/// use actix_web_opentelemetry::RequestMetrics;
+/// use opentelemetry::global;
+///
+/// # #[actix_web::main]
+/// # async fn main() -> std::io::Result<()> {
+/// let exporter = opentelemetry_prometheus::exporter().init();
+/// let meter = global::meter("actix_web");
+///
+/// // Optional predicate to determine which requests render the prometheus metrics
+/// let metrics_route = |req: &dev::ServiceRequest| {
+///     req.path() == "/metrics" && req.method() == http::Method::GET
+/// };
+///
+/// // Request metrics middleware
+/// let request_metrics = RequestMetrics::new(meter, Some(metrics_route), Some(exporter));
+///
+/// // Run actix server, metrics are now available at http://localhost:8080/metrics
+/// HttpServer::new(move || App::new().wrap(request_metrics.clone()))
+///     .bind("localhost:8080")?
+///     .run()
+///     .await
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct RequestMetrics {
+    route_formatter: Option<Arc<dyn RouteFormatter + Send + Sync + 'static>>,
+    metrics_route: Option<MetricsRouteFilter>,
+    exporter: Option<PrometheusExporter>,
+    http_request_duration: ValueRecorder<f64>,
+    http_request_count: Counter<u64>,
+    http_active_requests: BoundUpDownCounter<'static, i64>,
+}
+
+impl std::fmt::Debug for RequestMetrics {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RequestMetrics")
+            .field("route_formatter", &self.route_formatter)
+            .field(
+                "metrics_route",
+                &self.metrics_route.as_ref().map(|_| "Fn(&ServiceRequest) -> bool"),
+            )
+            .finish()
+    }
+}
+
+impl RequestMetrics {
+    /// Create a new `RequestMetrics`, registering instruments against `meter` and,
+    /// when both `metrics_route` and `exporter` are given, serving a Prometheus
+    /// scrape endpoint at the routes matched by `metrics_route`.
+    pub fn new<F>(
+        meter: Meter,
+        metrics_route: Option<F>,
+        exporter: Option<PrometheusExporter>,
+    ) -> Self
+    where
+        F: Fn(&ServiceRequest) -> bool + Send + Sync + 'static,
+    {
+        let (http_request_duration, http_request_count, http_active_requests) =
+            Self::build_instruments(&meter);
+        RequestMetrics {
+            route_formatter: None,
+            metrics_route: metrics_route.map(|f| Arc::new(f) as MetricsRouteFilter),
+            exporter,
+            http_request_duration,
+            http_request_count,
+            http_active_requests,
+        }
+    }
+
+    /// Start building a `RequestMetrics` middleware, e.g. to wire up an OTLP
+    /// push exporter instead of the Prometheus pull exporter.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use actix_web_opentelemetry::{OtlpProtocol, RequestMetrics};
+    /// use std::time::Duration;
+    ///
+    /// let request_metrics = RequestMetrics::builder()
+    ///     .with_otlp("http://localhost:4317", OtlpProtocol::Grpc, Duration::from_secs(10))
+    ///     .build();
+    /// ```
+    pub fn builder() -> RequestMetricsBuilder {
+        RequestMetricsBuilder::default()
+    }
+
+    fn build_instruments(meter: &Meter) -> RequestInstruments {
+        let http_request_duration = meter
+            .f64_value_recorder(HTTP_SERVER_DURATION)
+            .with_description("The duration of the inbound HTTP request")
+            .init();
+        let http_request_count = meter
+            .u64_counter(HTTP_SERVER_REQUEST_COUNT)
+            .with_description("The number of inbound HTTP requests received")
+            .init();
+        let http_active_requests = meter
+            .i64_up_down_counter(HTTP_SERVER_ACTIVE_REQUESTS)
+            .with_description("The number of concurrent inbound HTTP requests being processed")
+            .init()
+            .bind(&[]);
+        (http_request_duration, http_request_count, http_active_requests)
+    }
+}
+
+/// Builder for [`RequestMetrics`], used to configure an OTLP push-metrics
+/// exporter instead of the Prometheus pull exporter.
+///
+/// A `RequestMetrics` built this way never serves a Prometheus scrape
+/// response, with or without [`RequestMetricsBuilder::with_otlp`]; only
+/// [`RequestMetrics::new`] takes a Prometheus exporter.
+#[derive(Default, Debug)]
+pub struct RequestMetricsBuilder {
+    route_formatter: Option<Arc<dyn RouteFormatter + Send + Sync + 'static>>,
+    otlp: Option<(String, OtlpProtocol, Duration)>,
+}
+
+impl RequestMetricsBuilder {
+    /// Create a new `RequestMetricsBuilder`.
+    pub fn new() -> Self {
+        RequestMetricsBuilder::default()
+    }
+
+    /// Format routes with the given formatter before recording them as the
+    /// `http.route` attribute.
+    pub fn with_route_formatter<T: RouteFormatter + Send + Sync + 'static>(
+        mut self,
+        route_formatter: T,
+    ) -> Self {
+        self.route_formatter = Some(Arc::new(route_formatter));
+        self
+    }
+
+    /// Push metrics to an OpenTelemetry Collector at `endpoint` over `protocol`,
+    /// exporting once per `interval`, instead of serving a Prometheus scrape
+    /// endpoint. No `/metrics` route is installed in this mode.
+    pub fn with_otlp(mut self, endpoint: impl Into<String>, protocol: OtlpProtocol, interval: Duration) -> Self {
+        self.otlp = Some((endpoint.into(), protocol, interval));
+        self
+    }
+
+    /// Build the `RequestMetrics` middleware.
+    pub fn build(self) -> RequestMetrics {
+        let meter = match self.otlp {
+            Some((endpoint, protocol, interval)) => build_otlp_meter(&endpoint, protocol, interval),
+            None => global::meter("actix-web-opentelemetry"),
+        };
+        let (http_request_duration, http_request_count, http_active_requests) =
+            RequestMetrics::build_instruments(&meter);
+        RequestMetrics {
+            route_formatter: self.route_formatter,
+            // OTLP is push-based: there is no scrape endpoint to serve.
+            metrics_route: None,
+            exporter: None,
+            http_request_duration,
+            http_request_count,
+            http_active_requests,
+        }
+    }
+}
+
+/// Install a periodic OTLP metrics pipeline and return a meter backed by it.
+///
+/// `opentelemetry-otlp`'s metrics pipeline builds its tonic transport
+/// eagerly, and tonic's `Channel::new` spawns onto whatever tokio *1.x*
+/// runtime is current on the calling thread - but this crate (and actix-web
+/// 3 / awc 2) run entirely on actix-rt's tokio *0.2* executor, so calling
+/// this directly from a request handler panics with "there is no reactor
+/// running, must be called from the context of a Tokio 1.x runtime". To
+/// give the pipeline a real tokio 1.x runtime to build and run against, it's
+/// driven on a dedicated background thread, parked for the life of the
+/// process; only the resulting `Meter` (safe to use from any executor) is
+/// handed back to the caller.
+fn build_otlp_meter(endpoint: &str, protocol: OtlpProtocol, interval: Duration) -> Meter {
+    // `opentelemetry-otlp`'s metrics pipeline only supports a tonic
+    // exporter, see `OtlpProtocol::HttpProtobuf`.
+    let endpoint = match protocol {
+        OtlpProtocol::Grpc | OtlpProtocol::HttpProtobuf => endpoint.to_string(),
+    };
+    let (meter_tx, meter_rx) = std::sync::mpsc::channel();
+    std::thread::Builder::new()
+        .name("otlp-metrics".to_string())
+        .spawn(move || {
+            let rt = tokio1::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .expect("failed to start the OTLP metrics export runtime");
+            rt.block_on(async {
+                let exporter = opentelemetry_otlp::new_exporter()
+                    .tonic()
+                    .with_endpoint(endpoint);
+                let controller = opentelemetry_otlp::new_pipeline()
+                    .metrics(tokio1::spawn, opentelemetry::util::tokio_interval_stream)
+                    .with_exporter(exporter)
+                    .with_period(interval)
+                    .build()
+                    .expect("failed to install OTLP metrics pipeline");
+                let meter = controller.provider().meter("actix-web-opentelemetry", None);
+                // Keep the controller's periodic export task alive; it would
+                // otherwise shut down as soon as it's dropped here.
+                Box::leak(Box::new(controller));
+                let _ = meter_tx.send(meter);
+                // Keep this thread (and its runtime) parked for the life of
+                // the process instead of returning, so the leaked
+                // controller's spawned export task keeps running.
+                std::future::pending::<()>().await;
+            });
+        })
+        .expect("failed to spawn the OTLP metrics export thread");
+    meter_rx
+        .recv()
+        .expect("OTLP metrics export thread exited before producing a meter")
+}
+
+impl<S, B> Transform<S> for RequestMetrics
+where
+    S: Service<Request = ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = RequestMetricsMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ok(RequestMetricsMiddleware {
+            service,
+            inner: self.clone(),
+        })
+    }
+}
+
+/// Request metrics middleware.
+#[derive(Debug)]
+pub struct RequestMetricsMiddleware<S> {
+    service: S,
+    inner: RequestMetrics,
+}
+
+impl<S, B> Service for RequestMetricsMiddleware<S>
+where
+    S: Service<Request = ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    fn poll_ready(&mut self, cx: &mut std::task::Context<'_>) -> std::task::Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: ServiceRequest) -> Self::Future {
+        if let (Some(metrics_route), Some(exporter)) =
+            (&self.inner.metrics_route, &self.inner.exporter)
+        {
+            if metrics_route(&req) {
+                let response = serve_prometheus_metrics(exporter);
+                return Box::pin(async move { Ok(req.into_response(response.into_body())) });
+            }
+        }
+
+        let timer = SystemTime::now();
+        self.inner.http_active_requests.add(1);
+
+        let mut http_route = req.match_pattern().unwrap_or_else(|| "default".to_string());
+        if let Some(formatter) = &self.inner.route_formatter {
+            http_route = formatter.format(&http_route);
+        }
+        let method = req.method().to_string();
+
+        let inner = self.inner.clone();
+        let fut = self.service.call(req);
+        Box::pin(async move {
+            let res = fut.await;
+            inner.http_active_requests.add(-1);
+            let attributes = [
+                HTTP_METHOD.string(method),
+                HTTP_ROUTE.string(http_route),
+                HTTP_STATUS_CODE.i64(
+                    res.as_ref()
+                        .map(|res| res.status().as_u16())
+                        .unwrap_or(500) as i64,
+                ),
+            ];
+            inner.http_request_count.add(1, &attributes);
+            if let Ok(elapsed) = timer.elapsed() {
+                inner
+                    .http_request_duration
+                    .record(elapsed.as_secs_f64(), &attributes);
+            }
+            res
+        })
+    }
+}
+
+fn serve_prometheus_metrics(exporter: &PrometheusExporter) -> HttpResponse {
+    use prometheus::{Encoder, TextEncoder};
+
+    let metric_families = exporter.registry().gather();
+    let encoder = TextEncoder::new();
+    let mut buf = Vec::new();
+    if encoder.encode(&metric_families, &mut buf).is_err() {
+        return HttpResponse::InternalServerError().finish();
+    }
+    HttpResponse::Ok()
+        .content_type(encoder.format_type())
+        .body(buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn with_otlp_configures_push_metrics_builder() {
+        let builder = RequestMetricsBuilder::new().with_otlp(
+            "http://localhost:4317",
+            OtlpProtocol::Grpc,
+            Duration::from_secs(10),
+        );
+        assert!(builder.otlp.is_some());
+
+        let metrics = builder.build();
+        // OTLP is push-based: there is no scrape endpoint to serve.
+        assert!(metrics.metrics_route.is_none());
+        assert!(metrics.exporter.is_none());
+    }
+}
@@ -0,0 +1,4 @@
+#[cfg(feature = "metrics")]
+pub(crate) mod metrics;
+pub(crate) mod route_formatter;
+pub(crate) mod trace;
@@ -0,0 +1,18 @@
+/// Formats the span name (and Prometheus `http.route` label) for a matched route,
+/// e.g. to normalize path parameters before they're recorded as telemetry.
+pub trait RouteFormatter {
+    /// Formats the given route.
+    fn format(&self, path: &str) -> String;
+}
+
+impl std::fmt::Debug for dyn RouteFormatter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "RouteFormatter")
+    }
+}
+
+impl std::fmt::Debug for dyn RouteFormatter + Send + Sync {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "RouteFormatter")
+    }
+}
@@ -1,16 +1,19 @@
 use super::route_formatter::RouteFormatter;
-use actix_web::dev::{Service, ServiceRequest, ServiceResponse, Transform};
-use actix_web::{http::header, Error};
+use actix_web::dev::{Body, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::{
+    http::{header, StatusCode as HttpStatusCode},
+    Error,
+};
 use futures::{
     future::{ok, FutureExt, Ready},
     Future,
 };
-use opentelemetry::api::{
+use opentelemetry::{
+    global,
     propagation::Extractor,
     trace::{FutureExt as OtelFutureExt, SpanKind, StatusCode, TraceContextExt, Tracer},
-    Context,
+    Context, KeyValue,
 };
-use opentelemetry::global;
 use opentelemetry_semantic_conventions::trace::{
     HTTP_CLIENT_IP, HTTP_FLAVOR, HTTP_HOST, HTTP_METHOD, HTTP_ROUTE, HTTP_SCHEME, HTTP_SERVER_NAME,
     HTTP_STATUS_CODE, HTTP_TARGET, HTTP_USER_AGENT, NET_HOST_PORT, NET_PEER_IP,
@@ -19,6 +22,36 @@ use std::pin::Pin;
 use std::rc::Rc;
 use std::task::Poll;
 
+/// A function used to map an HTTP response status onto an OpenTelemetry
+/// [`StatusCode`], used by [`RequestTracing::with_status_mapper`].
+type StatusMapper = dyn Fn(HttpStatusCode, u16) -> StatusCode;
+
+/// A predicate used to decide whether a request is traced, used by
+/// [`RequestTracing::with_filter`].
+type RouteFilter = Rc<dyn Fn(&ServiceRequest) -> bool>;
+
+/// Extra span attributes derived from the request, used by
+/// [`RequestTracing::with_request_attrs`].
+type RequestAttrsFn = Rc<dyn Fn(&ServiceRequest) -> Vec<KeyValue>>;
+
+/// Extra span attributes derived from the response, used by
+/// [`RequestTracing::with_response_attrs`].
+type ResponseAttrsFn<B> = Rc<dyn Fn(&ServiceResponse<B>) -> Vec<KeyValue>>;
+
+/// Maps an HTTP response status onto an OpenTelemetry span status following
+/// the [OTel HTTP semantic conventions] for server spans: only a 5xx status
+/// marks the span as an error, 1xx-4xx responses are client-facing outcomes
+/// and leave the span status unset.
+///
+/// [OTel HTTP semantic conventions]: https://github.com/open-telemetry/opentelemetry-specification/blob/main/specification/trace/semantic_conventions/http.md#status
+fn default_status_mapper(_status: HttpStatusCode, status_code: u16) -> StatusCode {
+    if (500..600).contains(&status_code) {
+        StatusCode::Error
+    } else {
+        StatusCode::Unset
+    }
+}
+
 /// Request tracing middleware.
 ///
 /// # Examples:
@@ -36,7 +69,7 @@ use std::task::Poll;
 ///     // Install an OpenTelemetry trace pipeline.
 ///     // Swap for https://docs.rs/opentelemetry-jaeger or other compatible
 ///     // exporter to send trace information to your collector.
-///     opentelemetry::exporter::trace::stdout::new_pipeline().install();
+///     opentelemetry::sdk::export::trace::stdout::new_pipeline().install_simple();
 ///
 ///     HttpServer::new(|| {
 ///         App::new()
@@ -48,14 +81,71 @@ use std::task::Poll;
 ///     .await
 /// }
 ///```
-#[derive(Default, Debug)]
-pub struct RequestTracing {
+pub struct RequestTracing<B = Body> {
     route_formatter: Option<Rc<dyn RouteFormatter + 'static>>,
+    filter: Option<RouteFilter>,
+    status_mapper: Option<Rc<StatusMapper>>,
+    request_attrs: Option<RequestAttrsFn>,
+    response_attrs: Option<ResponseAttrsFn<B>>,
+}
+
+impl<B> Default for RequestTracing<B> {
+    fn default() -> Self {
+        RequestTracing {
+            route_formatter: None,
+            filter: None,
+            status_mapper: None,
+            request_attrs: None,
+            response_attrs: None,
+        }
+    }
 }
 
-impl RequestTracing {
+impl<B> Clone for RequestTracing<B> {
+    fn clone(&self) -> Self {
+        RequestTracing {
+            route_formatter: self.route_formatter.clone(),
+            filter: self.filter.clone(),
+            status_mapper: self.status_mapper.clone(),
+            request_attrs: self.request_attrs.clone(),
+            response_attrs: self.response_attrs.clone(),
+        }
+    }
+}
+
+impl<B> std::fmt::Debug for RequestTracing<B> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RequestTracing")
+            .field("route_formatter", &self.route_formatter)
+            .field("filter", &self.filter.as_ref().map(|_| "Fn(&ServiceRequest) -> bool"))
+            .field(
+                "status_mapper",
+                &self
+                    .status_mapper
+                    .as_ref()
+                    .map(|_| "Fn(StatusCode, u16) -> StatusCode"),
+            )
+            .field(
+                "request_attrs",
+                &self
+                    .request_attrs
+                    .as_ref()
+                    .map(|_| "Fn(&ServiceRequest) -> Vec<KeyValue>"),
+            )
+            .field(
+                "response_attrs",
+                &self
+                    .response_attrs
+                    .as_ref()
+                    .map(|_| "Fn(&ServiceResponse<B>) -> Vec<KeyValue>"),
+            )
+            .finish()
+    }
+}
+
+impl<B> RequestTracing<B> {
     /// Actix web middleware to trace each request in an OpenTelemetry span.
-    pub fn new() -> RequestTracing {
+    pub fn new() -> RequestTracing<B> {
         RequestTracing::default()
     }
 
@@ -84,7 +174,7 @@ impl RequestTracing {
     /// // report /users/{id} as /users/:id
     /// HttpServer::new(move || {
     ///     App::new()
-    ///         .wrap(RequestTracing::with_formatter(MyLowercaseFormatter))
+    ///         .wrap(RequestTracing::new().with_formatter(MyLowercaseFormatter))
     ///         .service(web::resource("/users/{id}").to(|| async { "ok" }))
     /// })
     /// .bind("127.0.0.1:8080")?
@@ -92,14 +182,173 @@ impl RequestTracing {
     /// .await
     /// # }
     /// ```
-    pub fn with_formatter<T: RouteFormatter + 'static>(route_formatter: T) -> Self {
-        RequestTracing {
-            route_formatter: Some(Rc::new(route_formatter)),
-        }
+    pub fn with_formatter<T: RouteFormatter + 'static>(mut self, route_formatter: T) -> Self {
+        self.route_formatter = Some(Rc::new(route_formatter));
+        self
+    }
+
+    /// Actix web middleware to trace each request in an OpenTelemetry span,
+    /// skipping requests for which `filter` returns `false`.
+    ///
+    /// This mirrors the predicate accepted by `RequestMetrics`, letting
+    /// infrastructure endpoints such as health checks or a Prometheus
+    /// `/metrics` scrape be excluded from traces. Filtered requests are
+    /// forwarded straight to the inner service: no span is built and no
+    /// propagation context is extracted, so they add zero tracing overhead.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use actix_web::{dev, web, App, HttpServer};
+    /// use actix_web_opentelemetry::RequestTracing;
+    ///
+    /// # #[actix_web::main]
+    /// # async fn main() -> std::io::Result<()> {
+    /// HttpServer::new(|| {
+    ///     App::new()
+    ///         .wrap(RequestTracing::new().with_filter(|req: &dev::ServiceRequest| {
+    ///             req.path() != "/health"
+    ///         }))
+    ///         .service(web::resource("/").to(|| async { "ok" }))
+    /// })
+    /// .bind("127.0.0.1:8080")?
+    /// .run()
+    /// .await
+    /// # }
+    /// ```
+    pub fn with_filter<F: Fn(&ServiceRequest) -> bool + 'static>(mut self, filter: F) -> Self {
+        self.filter = Some(Rc::new(filter));
+        self
+    }
+
+    /// Actix web middleware to trace each request in an OpenTelemetry span,
+    /// using `mapper` to decide the resulting span status instead of the
+    /// default [OTel HTTP semantic conventions] mapping (only 5xx responses
+    /// are recorded as errors).
+    ///
+    /// `mapper` receives the response's [`actix_web::http::StatusCode`] and
+    /// its numeric value, and returns the [`StatusCode`] to set on the span.
+    ///
+    /// [OTel HTTP semantic conventions]: https://github.com/open-telemetry/opentelemetry-specification/blob/main/specification/trace/semantic_conventions/http.md#status
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use actix_web::{http, web, App, HttpServer};
+    /// use actix_web_opentelemetry::RequestTracing;
+    /// use opentelemetry::trace::StatusCode;
+    ///
+    /// # #[actix_web::main]
+    /// # async fn main() -> std::io::Result<()> {
+    /// HttpServer::new(|| {
+    ///     App::new()
+    ///         .wrap(RequestTracing::new().with_status_mapper(
+    ///             |_status: http::StatusCode, code: u16| match code {
+    ///                 400..=599 => StatusCode::Error,
+    ///                 _ => StatusCode::Unset,
+    ///             },
+    ///         ))
+    ///         .service(web::resource("/").to(|| async { "ok" }))
+    /// })
+    /// .bind("127.0.0.1:8080")?
+    /// .run()
+    /// .await
+    /// # }
+    /// ```
+    pub fn with_status_mapper<F: Fn(HttpStatusCode, u16) -> StatusCode + 'static>(
+        mut self,
+        mapper: F,
+    ) -> Self {
+        self.status_mapper = Some(Rc::new(mapper));
+        self
+    }
+
+    /// Actix web middleware to trace each request in an OpenTelemetry span,
+    /// enriching the span with custom attributes derived from the live
+    /// request, e.g. a tenant id pulled from an extracted extension or an
+    /// `x-request-id` header.
+    ///
+    /// `request_attrs` is invoked after the default HTTP semantic-convention
+    /// attributes are set, and its attributes are pushed onto the span.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use actix_web::{web, App, HttpServer};
+    /// use actix_web_opentelemetry::RequestTracing;
+    /// use opentelemetry::KeyValue;
+    ///
+    /// # #[actix_web::main]
+    /// # async fn main() -> std::io::Result<()> {
+    /// HttpServer::new(|| {
+    ///     App::new()
+    ///         .wrap(RequestTracing::new().with_request_attrs(|req| {
+    ///             req.headers()
+    ///                 .get("x-request-id")
+    ///                 .and_then(|v| v.to_str().ok())
+    ///                 .map(|id| vec![KeyValue::new("x-request-id", id.to_string())])
+    ///                 .unwrap_or_default()
+    ///         }))
+    ///         .service(web::resource("/").to(|| async { "ok" }))
+    /// })
+    /// .bind("127.0.0.1:8080")?
+    /// .run()
+    /// .await
+    /// # }
+    /// ```
+    pub fn with_request_attrs<F: Fn(&ServiceRequest) -> Vec<KeyValue> + 'static>(
+        mut self,
+        request_attrs: F,
+    ) -> Self {
+        self.request_attrs = Some(Rc::new(request_attrs));
+        self
+    }
+
+    /// Actix web middleware to trace each request in an OpenTelemetry span,
+    /// enriching the span with custom attributes derived from the completed
+    /// response, e.g. response content-length or an authenticated subject.
+    ///
+    /// `response_attrs` is invoked right before the span ends, and its
+    /// attributes are pushed onto the span.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use actix_web::{web, App, HttpServer};
+    /// use actix_web_opentelemetry::RequestTracing;
+    /// use opentelemetry::KeyValue;
+    ///
+    /// # #[actix_web::main]
+    /// # async fn main() -> std::io::Result<()> {
+    /// HttpServer::new(|| {
+    ///     App::new()
+    ///         .wrap(RequestTracing::new().with_response_attrs(|res| {
+    ///             res.response()
+    ///                 .head()
+    ///                 .headers()
+    ///                 .get("content-length")
+    ///                 .and_then(|v| v.to_str().ok())
+    ///                 .and_then(|v| v.parse::<i64>().ok())
+    ///                 .map(|len| vec![KeyValue::new("http.response_content_length", len)])
+    ///                 .unwrap_or_default()
+    ///         }))
+    ///         .service(web::resource("/").to(|| async { "ok" }))
+    /// })
+    /// .bind("127.0.0.1:8080")?
+    /// .run()
+    /// .await
+    /// # }
+    /// ```
+    pub fn with_response_attrs<F: Fn(&ServiceResponse<B>) -> Vec<KeyValue> + 'static>(
+        mut self,
+        response_attrs: F,
+    ) -> Self {
+        self.response_attrs = Some(Rc::new(response_attrs));
+        self
     }
 }
 
-impl<S, B> Transform<S> for RequestTracing
+impl<S, B> Transform<S> for RequestTracing<B>
 where
     S: Service<Request = ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
     S::Future: 'static,
@@ -108,7 +357,7 @@ where
     type Request = ServiceRequest;
     type Response = ServiceResponse<B>;
     type Error = Error;
-    type Transform = RequestTracingMiddleware<S>;
+    type Transform = RequestTracingMiddleware<S, B>;
     type InitError = ();
     type Future = Ready<Result<Self::Transform, Self::InitError>>;
 
@@ -116,31 +365,80 @@ where
         ok(RequestTracingMiddleware::new(
             service,
             self.route_formatter.clone(),
+            self.filter.clone(),
+            self.status_mapper.clone(),
+            self.request_attrs.clone(),
+            self.response_attrs.clone(),
         ))
     }
 }
 
-#[derive(Debug)]
-pub struct RequestTracingMiddleware<S> {
+pub struct RequestTracingMiddleware<S, B> {
     service: S,
     route_formatter: Option<Rc<dyn RouteFormatter>>,
+    filter: Option<RouteFilter>,
+    status_mapper: Option<Rc<StatusMapper>>,
+    request_attrs: Option<RequestAttrsFn>,
+    response_attrs: Option<ResponseAttrsFn<B>>,
+}
+
+impl<S, B> std::fmt::Debug for RequestTracingMiddleware<S, B> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RequestTracingMiddleware")
+            .field("route_formatter", &self.route_formatter)
+            .field("filter", &self.filter.as_ref().map(|_| "Fn(&ServiceRequest) -> bool"))
+            .field(
+                "status_mapper",
+                &self
+                    .status_mapper
+                    .as_ref()
+                    .map(|_| "Fn(StatusCode, u16) -> StatusCode"),
+            )
+            .field(
+                "request_attrs",
+                &self
+                    .request_attrs
+                    .as_ref()
+                    .map(|_| "Fn(&ServiceRequest) -> Vec<KeyValue>"),
+            )
+            .field(
+                "response_attrs",
+                &self
+                    .response_attrs
+                    .as_ref()
+                    .map(|_| "Fn(&ServiceResponse<B>) -> Vec<KeyValue>"),
+            )
+            .finish()
+    }
 }
 
-impl<S, B> RequestTracingMiddleware<S>
+impl<S, B> RequestTracingMiddleware<S, B>
 where
     S: Service<Request = ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
     S::Future: 'static,
     B: 'static,
 {
-    fn new(service: S, route_formatter: Option<Rc<dyn RouteFormatter>>) -> Self {
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        service: S,
+        route_formatter: Option<Rc<dyn RouteFormatter>>,
+        filter: Option<RouteFilter>,
+        status_mapper: Option<Rc<StatusMapper>>,
+        request_attrs: Option<RequestAttrsFn>,
+        response_attrs: Option<ResponseAttrsFn<B>>,
+    ) -> Self {
         RequestTracingMiddleware {
             service,
             route_formatter,
+            filter,
+            status_mapper,
+            request_attrs,
+            response_attrs,
         }
     }
 }
 
-impl<S, B> Service for RequestTracingMiddleware<S>
+impl<S, B> Service for RequestTracingMiddleware<S, B>
 where
     S: Service<Request = ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
     S::Future: 'static,
@@ -156,6 +454,12 @@ where
     }
 
     fn call(&mut self, mut req: ServiceRequest) -> Self::Future {
+        if let Some(filter) = &self.filter {
+            if !filter(&req) {
+                return Box::pin(self.service.call(req));
+            }
+        }
+
         let _parent_context = global::get_text_map_propagator(|propagator| {
             propagator.extract(&RequestHeaderCarrier::new(req.headers_mut()))
         })
@@ -166,52 +470,59 @@ where
             http_route = formatter.format(&http_route);
         }
         let conn_info = req.connection_info();
-        let mut builder = tracer.span_builder(&http_route);
+        let host = conn_info.host().to_string();
+        let scheme = conn_info.scheme().to_string();
+        let remote_addr = conn_info.realip_remote_addr().map(str::to_string);
+        drop(conn_info);
+
+        let mut builder = tracer.span_builder(http_route.clone());
         builder.span_kind = Some(SpanKind::Server);
         let mut attributes = vec![
-            HTTP_METHOD.string(req.method().as_str()),
+            HTTP_METHOD.string(req.method().as_str().to_string()),
             HTTP_FLAVOR.string(format!("{:?}", req.version()).replace("HTTP/", "")),
-            HTTP_HOST.string(conn_info.host()),
+            HTTP_HOST.string(host.clone()),
             HTTP_ROUTE.string(http_route),
-            HTTP_SCHEME.string(conn_info.scheme()),
+            HTTP_SCHEME.string(scheme),
         ];
-        let server_name = req.app_config().host();
-        if server_name != conn_info.host() {
+        let server_name = req.app_config().host().to_string();
+        if server_name != host {
             attributes.push(HTTP_SERVER_NAME.string(server_name));
         }
-        if let Some(port) = conn_info
-            .host()
+        if let Some(port) = host
             .split_terminator(':')
             .nth(1)
-            .and_then(|port| port.parse().ok())
+            .and_then(|port| port.parse::<u16>().ok())
         {
-            attributes.push(NET_HOST_PORT.u64(port))
+            attributes.push(NET_HOST_PORT.i64(port as i64))
         }
         if let Some(path) = req.uri().path_and_query() {
-            attributes.push(HTTP_TARGET.string(path.as_str()))
+            attributes.push(HTTP_TARGET.string(path.as_str().to_string()))
         }
         if let Some(user_agent) = req
             .headers()
             .get(header::USER_AGENT)
             .and_then(|s| s.to_str().ok())
         {
-            attributes.push(HTTP_USER_AGENT.string(user_agent))
+            attributes.push(HTTP_USER_AGENT.string(user_agent.to_string()))
         }
-        let remote_addr = conn_info.realip_remote_addr();
-        if let Some(remote) = remote_addr {
+        if let Some(remote) = remote_addr.clone() {
             attributes.push(HTTP_CLIENT_IP.string(remote))
         }
         if let Some(peer_addr) = req.peer_addr().map(|socket| socket.to_string()) {
-            if Some(peer_addr.as_str()) != remote_addr {
+            if Some(&peer_addr) != remote_addr.as_ref() {
                 // Client is going through a proxy
                 attributes.push(NET_PEER_IP.string(peer_addr))
             }
         }
+        if let Some(request_attrs) = &self.request_attrs {
+            attributes.extend(request_attrs(&req));
+        }
         builder.attributes = Some(attributes);
         let span = tracer.build(builder);
         let cx = Context::current_with_span(span);
-        drop(conn_info);
 
+        let status_mapper = self.status_mapper.clone();
+        let response_attrs = self.response_attrs.clone();
         let fut = self
             .service
             .call(req)
@@ -219,33 +530,30 @@ where
             .map(move |res| match res {
                 Ok(ok_res) => {
                     let span = cx.span();
-                    span.set_attribute(HTTP_STATUS_CODE.u64(ok_res.status().as_u16() as u64));
-                    let status_code = match ok_res.status().as_u16() {
-                        100..=399 => StatusCode::OK,
-                        401 => StatusCode::Unauthenticated,
-                        403 => StatusCode::PermissionDenied,
-                        404 => StatusCode::NotFound,
-                        429 => StatusCode::ResourceExhausted,
-                        400..=499 => StatusCode::InvalidArgument,
-                        501 => StatusCode::Unimplemented,
-                        503 => StatusCode::Unavailable,
-                        504 => StatusCode::DeadlineExceeded,
-                        500..=599 => StatusCode::Internal,
-                        _ => StatusCode::Unknown,
-                    };
+                    let status = ok_res.status();
+                    span.set_attribute(HTTP_STATUS_CODE.i64(status.as_u16() as i64));
+                    let status_code = status_mapper
+                        .as_ref()
+                        .map(|mapper| mapper(status, status.as_u16()))
+                        .unwrap_or_else(|| default_status_mapper(status, status.as_u16()));
                     span.set_status(status_code, String::new());
+                    if let Some(response_attrs) = &response_attrs {
+                        for kv in response_attrs(&ok_res) {
+                            span.set_attribute(kv);
+                        }
+                    }
                     span.end();
                     Ok(ok_res)
                 }
                 Err(err) => {
                     let span = cx.span();
-                    span.set_status(StatusCode::Internal, format!("{:?}", err));
+                    span.set_status(StatusCode::Error, format!("{:?}", err));
                     span.end();
                     Err(err)
                 }
             });
 
-        Box::pin(async move { fut.await })
+        Box::pin(fut)
     }
 }
 
@@ -268,3 +576,40 @@ impl<'a> Extractor for RequestHeaderCarrier<'a> {
         self.headers.keys().map(|header| header.as_str()).collect()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_status_mapper_only_flags_server_errors() {
+        assert_eq!(
+            default_status_mapper(HttpStatusCode::OK, 200),
+            StatusCode::Unset
+        );
+        assert_eq!(
+            default_status_mapper(HttpStatusCode::NOT_FOUND, 404),
+            StatusCode::Unset
+        );
+        assert_eq!(
+            default_status_mapper(HttpStatusCode::INTERNAL_SERVER_ERROR, 500),
+            StatusCode::Error
+        );
+        assert_eq!(
+            default_status_mapper(HttpStatusCode::SERVICE_UNAVAILABLE, 503),
+            StatusCode::Error
+        );
+    }
+
+    #[test]
+    fn builder_methods_are_chainable() {
+        let tracing = RequestTracing::<Body>::new()
+            .with_filter(|req: &ServiceRequest| req.path() != "/health")
+            .with_status_mapper(|_status: HttpStatusCode, _code: u16| StatusCode::Unset)
+            .with_request_attrs(|_req| vec![]);
+
+        assert!(tracing.filter.is_some());
+        assert!(tracing.status_mapper.is_some());
+        assert!(tracing.request_attrs.is_some());
+    }
+}
@@ -0,0 +1 @@
+//! Internal helpers shared across middleware and client instrumentation.